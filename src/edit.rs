@@ -0,0 +1,266 @@
+use crate::*;
+use std::ops::Range;
+
+impl RubyString {
+    ///Finds the index in `self.placements` before which a plain-text offset of `plain_offset`
+    ///falls, i.e. the first placement (if any) with `text_start >= plain_offset`.
+    ///
+    ///Panics if `plain_offset` falls strictly inside a rubied segment, since cutting a gloss in
+    ///half is ill-defined.
+    fn placement_index_at(&self, plain_offset: usize) -> usize {
+        let idx = self
+            .placements
+            .partition_point(|p| p.text_end <= plain_offset);
+        if let Some(p) = self.placements.get(idx) {
+            if plain_offset > p.text_start {
+                panic!(
+                    "plain offset {} falls inside a rubied segment starting at {}",
+                    plain_offset, p.text_start
+                );
+            }
+        }
+        idx
+    }
+
+    ///Inserts `string` as plain text at `plain_offset`, which is a byte offset into the plain
+    ///text as returned by [`to_plain_text`](#method.to_plain_text).
+    ///
+    ///```
+    ///# use ruby_string::{RubyString, Segment};
+    ///let mut rs = RubyString::new();
+    ///rs.push_str("ここです");
+    ///rs.insert_str(3, "東京");
+    ///assert_eq!(rs.to_plain_text(), "こ東京こです");
+    ///```
+    ///
+    ///# Panics
+    ///
+    ///Panics if `plain_offset` is not on a char boundary of the plain text, or if it falls
+    ///strictly inside a rubied segment.
+    pub fn insert_str(&mut self, plain_offset: usize, string: &str) {
+        assert!(
+            self.packed_text.is_char_boundary(plain_offset),
+            "plain offset {} is not a char boundary",
+            plain_offset
+        );
+        let idx = self.placement_index_at(plain_offset);
+        self.packed_text.insert_str(plain_offset, string);
+        let shift = string.len();
+        for p in &mut self.placements[idx..] {
+            p.text_start += shift;
+            p.text_end += shift;
+        }
+    }
+
+    ///Inserts `segment` at `plain_offset`, which is a byte offset into the plain text as returned
+    ///by [`to_plain_text`](#method.to_plain_text).
+    ///
+    ///```
+    ///# use ruby_string::{RubyString, Segment};
+    ///let mut rs = RubyString::new();
+    ///rs.push_str("ここです");
+    ///rs.insert_segment(3, Segment::Rubied { text: "東京", ruby: "とうきょう" });
+    ///assert_eq!(rs.to_plain_text(), "こ東京こです");
+    ///assert_eq!(rs.segments().nth(1), Some(Segment::Rubied { text: "東京", ruby: "とうきょう" }));
+    ///```
+    ///
+    ///# Panics
+    ///
+    ///Panics if `plain_offset` is not on a char boundary of the plain text, or if it falls
+    ///strictly inside an existing rubied segment.
+    pub fn insert_segment(&mut self, plain_offset: usize, segment: Segment<'_>) {
+        let Segment::Rubied { text, ruby } = segment else {
+            return self.insert_str(plain_offset, segment.plain_text());
+        };
+        assert!(
+            self.packed_text.is_char_boundary(plain_offset),
+            "plain offset {} is not a char boundary",
+            plain_offset
+        );
+        let idx = self.placement_index_at(plain_offset);
+        let ruby_offset = self
+            .placements
+            .get(idx)
+            .map_or(self.packed_ruby.len(), |p| p.ruby_start);
+
+        self.packed_text.insert_str(plain_offset, text);
+        self.packed_ruby.insert_str(ruby_offset, ruby);
+        for p in &mut self.placements[idx..] {
+            p.text_start += text.len();
+            p.text_end += text.len();
+            p.ruby_start += ruby.len();
+            p.ruby_end += ruby.len();
+        }
+        self.placements.insert(
+            idx,
+            Placement {
+                text_start: plain_offset,
+                text_end: plain_offset + text.len(),
+                ruby_start: ruby_offset,
+                ruby_end: ruby_offset + ruby.len(),
+            },
+        );
+    }
+
+    ///Shortens the plain text to `plain_len` bytes, dropping everything after that point, like
+    ///[`String::truncate`](https://doc.rust-lang.org/std/string/struct.String.html#method.truncate).
+    ///
+    ///```
+    ///# use ruby_string::{RubyString, Segment};
+    ///let mut rs = RubyString::new();
+    ///rs.push_str("ここは");
+    ///rs.push_segment(Segment::Rubied { text: "東京", ruby: "とうきょう" });
+    ///rs.truncate(9);
+    ///assert_eq!(rs.to_plain_text(), "ここは");
+    ///```
+    ///
+    ///# Panics
+    ///
+    ///Panics if `plain_len` is not on a char boundary of the plain text, or if it falls strictly
+    ///inside a rubied segment.
+    pub fn truncate(&mut self, plain_len: usize) {
+        assert!(
+            self.packed_text.is_char_boundary(plain_len),
+            "plain offset {} is not a char boundary",
+            plain_len
+        );
+        let idx = self.placement_index_at(plain_len);
+        let ruby_len = self
+            .placements
+            .get(idx)
+            .map_or(self.packed_ruby.len(), |p| p.ruby_start);
+        self.packed_text.truncate(plain_len);
+        self.packed_ruby.truncate(ruby_len);
+        self.placements.truncate(idx);
+    }
+
+    ///Replaces the plain text in `plain_range` with `replacement`, like
+    ///[`String::replace_range`](https://doc.rust-lang.org/std/string/struct.String.html#method.replace_range).
+    ///`plain_range` is a range of byte offsets into the plain text as returned by
+    ///[`to_plain_text`](#method.to_plain_text); any rubied segment fully contained in the range is
+    ///removed along with its gloss.
+    ///
+    ///```
+    ///# use ruby_string::{RubyString, Segment};
+    ///let mut rs = RubyString::new();
+    ///rs.push_str("ここは");
+    ///rs.push_segment(Segment::Rubied { text: "東京", ruby: "とうきょう" });
+    ///rs.push_str("です");
+    ///rs.replace_plain_range(9..15, "大阪");
+    ///assert_eq!(rs.to_plain_text(), "ここは大阪です");
+    ///assert_eq!(rs.segments().count(), 1);
+    ///```
+    ///
+    ///# Panics
+    ///
+    ///Panics if either end of `plain_range` is not on a char boundary of the plain text, or falls
+    ///strictly inside a rubied segment that is not fully contained in the range.
+    pub fn replace_plain_range(&mut self, plain_range: Range<usize>, replacement: &str) {
+        let Range { start, end } = plain_range;
+        assert!(start <= end, "plain range start {} > end {}", start, end);
+        assert!(
+            self.packed_text.is_char_boundary(start) && self.packed_text.is_char_boundary(end),
+            "plain range {}..{} is not on char boundaries",
+            start,
+            end
+        );
+        let start_idx = self.placement_index_at(start);
+        let end_idx = self.placement_index_at(end);
+
+        let ruby_start = self
+            .placements
+            .get(start_idx)
+            .map_or(self.packed_ruby.len(), |p| p.ruby_start);
+        let ruby_end = self
+            .placements
+            .get(end_idx)
+            .map_or(self.packed_ruby.len(), |p| p.ruby_start);
+
+        self.packed_ruby.replace_range(ruby_start..ruby_end, "");
+        self.packed_text.replace_range(start..end, replacement);
+        self.placements.drain(start_idx..end_idx);
+
+        let removed_ruby_len = ruby_end - ruby_start;
+        for p in &mut self.placements[start_idx..] {
+            p.text_start = p.text_start - end + start + replacement.len();
+            p.text_end = p.text_end - end + start + replacement.len();
+            p.ruby_start -= removed_ruby_len;
+            p.ruby_end -= removed_ruby_len;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rubied_rs() -> RubyString {
+        let mut rs = RubyString::new();
+        rs.push_str("ここは");
+        rs.push_segment(Segment::Rubied {
+            text: "東京",
+            ruby: "とうきょう",
+        });
+        rs.push_str("です");
+        rs
+    }
+
+    #[test]
+    #[should_panic(expected = "falls inside a rubied segment")]
+    fn insert_str_panics_inside_rubied_segment() {
+        let mut rs = rubied_rs();
+        //"東京" starts at byte 9; byte 12, one char into it, is strictly inside the segment
+        rs.insert_str(12, "x");
+    }
+
+    #[test]
+    #[should_panic(expected = "is not a char boundary")]
+    fn insert_str_panics_on_non_char_boundary() {
+        let mut rs = RubyString::from("あ");
+        rs.insert_str(1, "x");
+    }
+
+    #[test]
+    #[should_panic(expected = "falls inside a rubied segment")]
+    fn insert_segment_panics_inside_rubied_segment() {
+        let mut rs = rubied_rs();
+        rs.insert_segment(12, Segment::Rubied { text: "大阪", ruby: "おおさか" });
+    }
+
+    #[test]
+    #[should_panic(expected = "is not a char boundary")]
+    fn insert_segment_panics_on_non_char_boundary() {
+        let mut rs = RubyString::from("あ");
+        rs.insert_segment(1, Segment::Rubied { text: "大阪", ruby: "おおさか" });
+    }
+
+    #[test]
+    #[should_panic(expected = "falls inside a rubied segment")]
+    fn truncate_panics_inside_rubied_segment() {
+        let mut rs = rubied_rs();
+        rs.truncate(12);
+    }
+
+    #[test]
+    #[should_panic(expected = "is not a char boundary")]
+    fn truncate_panics_on_non_char_boundary() {
+        let mut rs = RubyString::from("あ");
+        rs.truncate(1);
+    }
+
+    #[test]
+    #[should_panic(expected = "falls inside a rubied segment")]
+    fn replace_plain_range_panics_on_partial_overlap_with_rubied_segment() {
+        let mut rs = rubied_rs();
+        //"東京" occupies plain bytes 9..15; a range ending at 12 only partially overlaps it
+        rs.replace_plain_range(9..12, "x");
+    }
+
+    #[test]
+    fn replace_plain_range_allows_fully_contained_rubied_segment() {
+        let mut rs = rubied_rs();
+        rs.replace_plain_range(9..15, "大阪");
+        assert_eq!(rs.to_plain_text(), "ここは大阪です");
+        assert_eq!(rs.segments().count(), 1);
+    }
+}
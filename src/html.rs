@@ -0,0 +1,373 @@
+use crate::*;
+use std::fmt;
+
+///Options for [`RubyString::to_html_with_options`](struct.RubyString.html#method.to_html_with_options).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HtmlOptions {
+    ///If true, the base text of each rubied segment is additionally wrapped in `<rb>...</rb>`,
+    ///for compatibility with consumers that expect the older, more explicit markup.
+    pub emit_rb_tags: bool,
+}
+
+///An error that can occur while parsing a `RubyString` from HTML via
+///[`RubyString::from_html`](struct.RubyString.html#method.from_html).
+///
+///The contained value is always a byte offset into the input: that of the offending tag or
+///character reference, or for `UnterminatedElement`, that of the `<ruby>` tag that was never
+///closed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HtmlError {
+    ///A tag appeared where it was not expected, e.g. a second `<ruby>` before the first one was
+    ///closed, or an `<rt>` outside of a `<ruby>` element.
+    UnexpectedTag(String, usize),
+    ///A closing tag appeared without a matching, still open opening tag.
+    UnexpectedClose(String, usize),
+    ///A `<` was not followed by a well-formed tag (i.e. a matching `>` was never found).
+    UnterminatedTag(usize),
+    ///The input ended while a `<ruby>` or `<rt>` element was still open.
+    UnterminatedElement(&'static str, usize),
+    ///An `&...;` character reference was malformed or did not name a supported entity.
+    InvalidEntity(usize),
+}
+
+impl fmt::Display for HtmlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HtmlError::UnexpectedTag(tag, offset) => {
+                write!(f, "unexpected <{}> tag at byte offset {}", tag, offset)
+            }
+            HtmlError::UnexpectedClose(tag, offset) => {
+                write!(f, "unexpected </{}> tag at byte offset {}", tag, offset)
+            }
+            HtmlError::UnterminatedTag(offset) => {
+                write!(f, "unterminated tag starting at byte offset {}", offset)
+            }
+            HtmlError::UnterminatedElement(tag, offset) => write!(
+                f,
+                "<{}> starting at byte offset {} was not closed",
+                tag, offset
+            ),
+            HtmlError::InvalidEntity(offset) => {
+                write!(f, "invalid character reference at byte offset {}", offset)
+            }
+        }
+    }
+}
+
+impl std::error::Error for HtmlError {}
+
+impl RubyString {
+    ///Serializes this `RubyString` as HTML, using the `<ruby>`/`<rt>` elements to represent ruby
+    ///glosses. This is equivalent to `to_html_with_options` with the default options.
+    ///
+    ///```
+    ///# use ruby_string::{RubyString, Segment};
+    ///let mut rs = RubyString::new();
+    ///rs.push_segment(Segment::Rubied { text: "東京", ruby: "とうきょう" });
+    ///rs.push_str(" & friends");
+    ///assert_eq!(
+    ///    rs.to_html(),
+    ///    "<ruby>東京<rt>とうきょう</rt></ruby> &amp; friends"
+    ///);
+    ///```
+    pub fn to_html(&self) -> String {
+        self.to_html_with_options(HtmlOptions::default())
+    }
+
+    ///Serializes this `RubyString` as HTML, like [`to_html`](#method.to_html), but allows
+    ///configuring the output via `options`.
+    pub fn to_html_with_options(&self, options: HtmlOptions) -> String {
+        let mut html = String::new();
+        for segment in self.segments() {
+            match segment {
+                Segment::Plain { text } => html.push_str(&escape_html(text)),
+                Segment::Rubied { text, ruby } => {
+                    html.push_str("<ruby>");
+                    if options.emit_rb_tags {
+                        html.push_str("<rb>");
+                        html.push_str(&escape_html(text));
+                        html.push_str("</rb>");
+                    } else {
+                        html.push_str(&escape_html(text));
+                    }
+                    html.push_str("<rt>");
+                    html.push_str(&escape_html(ruby));
+                    html.push_str("</rt></ruby>");
+                }
+            }
+        }
+        html
+    }
+
+    ///Parses a `RubyString` from a narrow subset of HTML that only understands the
+    ///`<ruby>`/`<rt>`/`<rp>`/`<rb>` tags, treating `<rp>` fallback-parenthesis content as
+    ///ignorable. This is the inverse of [`to_html`](#method.to_html) (and, with `emit_rb_tags`,
+    ///of [`to_html_with_options`](#method.to_html_with_options)).
+    ///
+    ///Because the parser only recognizes these four tags, it is safe to use on untrusted
+    ///fragments: any other tag, or any unexpected nesting of these tags, is rejected with an
+    ///error rather than silently passed through.
+    ///
+    ///```
+    ///# use ruby_string::RubyString;
+    ///let html = "<ruby>東京<rp>(</rp><rt>とうきょう</rt><rp>)</rp></ruby> &amp; friends";
+    ///let rs = RubyString::from_html(html).unwrap();
+    ///assert_eq!(rs.to_plain_text(), "東京 & friends");
+    ///```
+    pub fn from_html(html: &str) -> Result<RubyString, HtmlError> {
+        let mut result = RubyString::new();
+        let mut phase = HtmlParsePhase::Normal;
+        let mut in_rp = false;
+        //`None` before any `<rb>` has been seen in the current `<ruby>` element, `Some(true)`
+        //while one is open, `Some(false)` once it has been closed - `<rb>` may appear at most
+        //once per `<ruby>` element
+        let mut rb_state: Option<bool> = None;
+        let mut offset = 0;
+
+        while offset < html.len() {
+            if html.as_bytes()[offset] == b'<' {
+                let tag_start = offset;
+                let (tag, closing, tag_end) = parse_tag(html, offset)?;
+                offset = tag_end;
+                //`<rp>` only holds fallback text, so no other tag may appear inside it
+                if in_rp && !(tag == "rp" && closing) {
+                    return Err(if closing {
+                        HtmlError::UnexpectedClose(tag.into(), tag_start)
+                    } else {
+                        HtmlError::UnexpectedTag(tag.into(), tag_start)
+                    });
+                }
+                match tag {
+                    "ruby" if !closing => match phase {
+                        HtmlParsePhase::Normal => {
+                            rb_state = None;
+                            phase = HtmlParsePhase::Base {
+                                start_offset: tag_start,
+                                text: String::new(),
+                            }
+                        }
+                        _ => return Err(HtmlError::UnexpectedTag("ruby".into(), tag_start)),
+                    },
+                    "ruby" if closing => match phase {
+                        HtmlParsePhase::AfterGloss { text, ruby, .. } => {
+                            result.push_segment(Segment::Rubied {
+                                text: &text,
+                                ruby: &ruby,
+                            });
+                            phase = HtmlParsePhase::Normal;
+                        }
+                        _ => return Err(HtmlError::UnexpectedClose("ruby".into(), tag_start)),
+                    },
+                    "rb" if !closing => {
+                        //`<rb>` is a pure wrapper tag around the base text; it carries no
+                        //information beyond what the enclosing `<ruby>` already gives us, but we
+                        //still track whether it is open (and whether it was already used) so
+                        //that stray/duplicate/unmatched `<rb>`/`</rb>` tags are rejected rather
+                        //than silently accepted
+                        if rb_state.is_some() || !matches!(phase, HtmlParsePhase::Base { .. }) {
+                            return Err(HtmlError::UnexpectedTag("rb".into(), tag_start));
+                        }
+                        rb_state = Some(true);
+                    }
+                    "rb" if closing => {
+                        if rb_state != Some(true) {
+                            return Err(HtmlError::UnexpectedClose("rb".into(), tag_start));
+                        }
+                        rb_state = Some(false);
+                    }
+                    "rt" if !closing => match phase {
+                        HtmlParsePhase::Base { start_offset, text } if rb_state != Some(true) => {
+                            phase = HtmlParsePhase::Gloss {
+                                start_offset,
+                                text,
+                                ruby: String::new(),
+                            }
+                        }
+                        _ => return Err(HtmlError::UnexpectedTag("rt".into(), tag_start)),
+                    },
+                    "rt" if closing => match phase {
+                        HtmlParsePhase::Gloss {
+                            start_offset,
+                            text,
+                            ruby,
+                        } => {
+                            phase = HtmlParsePhase::AfterGloss {
+                                start_offset,
+                                text,
+                                ruby,
+                            }
+                        }
+                        _ => return Err(HtmlError::UnexpectedClose("rt".into(), tag_start)),
+                    },
+                    "rp" if !closing => {
+                        if matches!(phase, HtmlParsePhase::Normal) {
+                            return Err(HtmlError::UnexpectedTag("rp".into(), tag_start));
+                        }
+                        in_rp = true;
+                    }
+                    "rp" if closing => {
+                        if !in_rp {
+                            return Err(HtmlError::UnexpectedClose("rp".into(), tag_start));
+                        }
+                        in_rp = false;
+                    }
+                    _ => return Err(HtmlError::UnexpectedTag(tag.into(), tag_start)),
+                }
+            } else {
+                let next_tag = html[offset..].find('<').map_or(html.len(), |i| offset + i);
+                let raw_text = &html[offset..next_tag];
+                offset = next_tag;
+                if in_rp {
+                    continue;
+                }
+                let text = unescape_html(raw_text, offset - raw_text.len())?;
+                match &mut phase {
+                    HtmlParsePhase::Normal => result.push_str(&text),
+                    HtmlParsePhase::Base { text: base, .. } => base.push_str(&text),
+                    HtmlParsePhase::Gloss { ruby, .. } => ruby.push_str(&text),
+                    HtmlParsePhase::AfterGloss { .. } => {} //only <rp> fallback text is expected here
+                }
+            }
+        }
+
+        match phase {
+            HtmlParsePhase::Normal => Ok(result),
+            HtmlParsePhase::Base { start_offset, .. } => {
+                Err(HtmlError::UnterminatedElement("ruby", start_offset))
+            }
+            HtmlParsePhase::Gloss { start_offset, .. }
+            | HtmlParsePhase::AfterGloss { start_offset, .. } => {
+                Err(HtmlError::UnterminatedElement("ruby", start_offset))
+            }
+        }
+    }
+}
+
+///The state of the small state machine used by
+///[`RubyString::from_html`](struct.RubyString.html#method.from_html).
+enum HtmlParsePhase {
+    ///Outside any `<ruby>` element.
+    Normal,
+    ///Inside `<ruby>`, before `<rt>`, accumulating the base text.
+    Base { start_offset: usize, text: String },
+    ///Inside `<rt>`, accumulating the gloss.
+    Gloss {
+        start_offset: usize,
+        text: String,
+        ruby: String,
+    },
+    ///After `</rt>` but before `</ruby>`; only `<rp>` fallback content is expected here.
+    AfterGloss {
+        start_offset: usize,
+        text: String,
+        ruby: String,
+    },
+}
+
+///Parses the tag starting at `html[offset..]` (which must start with `<`), returning its name,
+///whether it is a closing tag, and the offset right after the tag's `>`.
+fn parse_tag(html: &str, offset: usize) -> Result<(&str, bool, usize), HtmlError> {
+    let tag_end = html[offset..]
+        .find('>')
+        .map(|i| offset + i + 1)
+        .ok_or(HtmlError::UnterminatedTag(offset))?;
+    let mut inner = &html[offset + 1..tag_end - 1];
+    let closing = inner.starts_with('/');
+    if closing {
+        inner = &inner[1..];
+    }
+    let name = inner.trim_end_matches('/').trim();
+    Ok((name, closing, tag_end))
+}
+
+///Escapes `&`, `<`, and `>` in `text` for use as HTML text content.
+fn escape_html(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            _ => result.push(ch),
+        }
+    }
+    result
+}
+
+///Unescapes the standard named and numeric character references in `text`. `base_offset` is the
+///byte offset of `text` within the original input, used for error reporting.
+fn unescape_html(text: &str, base_offset: usize) -> Result<String, HtmlError> {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    let mut consumed = 0;
+    while let Some(amp) = rest.find('&') {
+        result.push_str(&rest[..amp]);
+        let after_amp = &rest[amp + 1..];
+        let semi = after_amp
+            .find(';')
+            .ok_or(HtmlError::InvalidEntity(base_offset + consumed + amp))?;
+        let name = &after_amp[..semi];
+        let ch = match name {
+            "amp" => '&',
+            "lt" => '<',
+            "gt" => '>',
+            "quot" => '"',
+            "apos" => '\'',
+            _ if name.starts_with('#') => {
+                let code = if let Some(hex) = name[1..].strip_prefix(['x', 'X']) {
+                    u32::from_str_radix(hex, 16).ok()
+                } else {
+                    name[1..].parse::<u32>().ok()
+                };
+                code.and_then(char::from_u32)
+                    .ok_or(HtmlError::InvalidEntity(base_offset + consumed + amp))?
+            }
+            _ => return Err(HtmlError::InvalidEntity(base_offset + consumed + amp)),
+        };
+        result.push(ch);
+        consumed += amp + 1 + semi + 1;
+        rest = &after_amp[semi + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_html_rejects_unexpected_tag() {
+        let err = RubyString::from_html("<rt>foo</rt>").err();
+        assert_eq!(err, Some(HtmlError::UnexpectedTag("rt".into(), 0)));
+    }
+
+    #[test]
+    fn from_html_rejects_unexpected_close() {
+        let html = "<ruby>東京<rt>とうきょう</rt></ruby></ruby>";
+        let closed_once = "<ruby>東京<rt>とうきょう</rt></ruby>";
+        let err = RubyString::from_html(html).err();
+        assert_eq!(
+            err,
+            Some(HtmlError::UnexpectedClose("ruby".into(), closed_once.len()))
+        );
+    }
+
+    #[test]
+    fn from_html_rejects_unterminated_tag() {
+        let err = RubyString::from_html("<ruby").err();
+        assert_eq!(err, Some(HtmlError::UnterminatedTag(0)));
+    }
+
+    #[test]
+    fn from_html_rejects_unterminated_element() {
+        let err = RubyString::from_html("<ruby>abc").err();
+        assert_eq!(err, Some(HtmlError::UnterminatedElement("ruby", 0)));
+    }
+
+    #[test]
+    fn from_html_rejects_invalid_entity() {
+        let err = RubyString::from_html("foo &bogus; bar").err();
+        assert_eq!(err, Some(HtmlError::InvalidEntity(4)));
+    }
+}
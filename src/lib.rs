@@ -14,3 +14,8 @@ mod iterator;
 pub use iterator::*;
 mod string;
 pub use string::*;
+mod wrap;
+pub use wrap::*;
+mod html;
+pub use html::*;
+mod edit;
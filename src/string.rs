@@ -1,4 +1,5 @@
 use crate::*;
+use std::fmt;
 use std::iter::FromIterator;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -29,6 +30,83 @@ pub struct RubyString {
     pub(crate) placements: Vec<Placement>,
 }
 
+///The capacities of the three backing buffers of a `RubyString`, as returned by
+///[`RubyString::capacity`](struct.RubyString.html#method.capacity).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RubyStringCapacity {
+    ///Capacity, in bytes, of the buffer holding the plain text.
+    pub text_bytes: usize,
+    ///Capacity, in bytes, of the buffer holding the ruby glosses.
+    pub ruby_bytes: usize,
+    ///Capacity, in number of entries, of the list of rubied segment placements.
+    pub segments: usize,
+}
+
+///An error that can occur while parsing a `RubyString` from a serialized encoding, e.g. via
+///[`RubyString::from_interlinear_encoding`](struct.RubyString.html#method.from_interlinear_encoding).
+///
+///The contained value is always a byte offset into the input: that of the offending character,
+///or for `UnterminatedAnnotation`, that of the U+FFF9 anchor of the annotation that was never
+///terminated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    ///A U+FFFA (interlinear annotation separator) appeared without a preceding, still open
+    ///U+FFF9 (interlinear annotation anchor).
+    UnexpectedAnnotationSeparator(usize),
+    ///A U+FFFB (interlinear annotation terminator) appeared without a preceding, still open
+    ///U+FFF9 (interlinear annotation anchor).
+    UnexpectedAnnotationTerminator(usize),
+    ///A U+FFF9 (interlinear annotation anchor) appeared while a previous annotation was still
+    ///open.
+    UnexpectedAnnotationAnchor(usize),
+    ///The input ended while an annotation that started at this offset was still open.
+    UnterminatedAnnotation(usize),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ParseError::UnexpectedAnnotationSeparator(offset) => write!(
+                f,
+                "unexpected interlinear annotation separator (U+FFFA) at byte offset {}",
+                offset
+            ),
+            ParseError::UnexpectedAnnotationTerminator(offset) => write!(
+                f,
+                "unexpected interlinear annotation terminator (U+FFFB) at byte offset {}",
+                offset
+            ),
+            ParseError::UnexpectedAnnotationAnchor(offset) => write!(
+                f,
+                "unexpected interlinear annotation anchor (U+FFF9) at byte offset {}",
+                offset
+            ),
+            ParseError::UnterminatedAnnotation(offset) => write!(
+                f,
+                "interlinear annotation starting at byte offset {} was not terminated",
+                offset
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+///The state of the small state machine used by
+///[`RubyString::from_interlinear_encoding`](struct.RubyString.html#method.from_interlinear_encoding).
+enum InterlinearParseState<'a> {
+    ///Accumulating plain text. The value is the byte offset where the current plain run started.
+    Normal(usize),
+    ///Accumulating the base text of an open annotation. The values are the byte offset where the
+    ///annotation (i.e. its U+FFF9 anchor) started, and the byte offset where the base text
+    ///started.
+    Base(usize, usize),
+    ///Accumulating the gloss of an open annotation. The values are the byte offset where the
+    ///annotation started, the base text collected so far, and the byte offset where the gloss
+    ///started.
+    Ruby(usize, &'a str, usize),
+}
+
 impl RubyString {
     ///Creates a new empty `RubyString`.
     pub fn new() -> RubyString {
@@ -39,6 +117,64 @@ impl RubyString {
         }
     }
 
+    ///Creates a new empty `RubyString` with at least the specified capacity in each of its three
+    ///backing buffers: `text_bytes` for the plain text, `ruby_bytes` for the ruby glosses, and
+    ///`segments` for the number of rubied segments. This avoids the repeated reallocations that
+    ///would otherwise occur while building up a large `RubyString` segment by segment, e.g. when
+    ///parsing a document of a known size.
+    pub fn with_capacity(text_bytes: usize, ruby_bytes: usize, segments: usize) -> RubyString {
+        RubyString {
+            packed_text: String::with_capacity(text_bytes),
+            packed_ruby: String::with_capacity(ruby_bytes),
+            placements: Vec::with_capacity(segments),
+        }
+    }
+
+    ///Returns the capacities of the three backing buffers of this `RubyString`.
+    pub fn capacity(&self) -> RubyStringCapacity {
+        RubyStringCapacity {
+            text_bytes: self.packed_text.capacity(),
+            ruby_bytes: self.packed_ruby.capacity(),
+            segments: self.placements.capacity(),
+        }
+    }
+
+    ///Reserves capacity for at least `additional_text_bytes` more bytes of plain text,
+    ///`additional_ruby_bytes` more bytes of ruby glosses, and `additional_segments` more rubied
+    ///segments to be inserted into this `RubyString`. Like
+    ///[`String::reserve`](https://doc.rust-lang.org/std/string/struct.String.html#method.reserve),
+    ///the backing buffers may reserve more space to avoid frequent reallocations.
+    pub fn reserve(
+        &mut self,
+        additional_text_bytes: usize,
+        additional_ruby_bytes: usize,
+        additional_segments: usize,
+    ) {
+        self.packed_text.reserve(additional_text_bytes);
+        self.packed_ruby.reserve(additional_ruby_bytes);
+        self.placements.reserve(additional_segments);
+    }
+
+    ///Like [`reserve`](#method.reserve), but does not over-allocate, analogous to
+    ///[`String::reserve_exact`](https://doc.rust-lang.org/std/string/struct.String.html#method.reserve_exact).
+    pub fn reserve_exact(
+        &mut self,
+        additional_text_bytes: usize,
+        additional_ruby_bytes: usize,
+        additional_segments: usize,
+    ) {
+        self.packed_text.reserve_exact(additional_text_bytes);
+        self.packed_ruby.reserve_exact(additional_ruby_bytes);
+        self.placements.reserve_exact(additional_segments);
+    }
+
+    ///Shrinks the capacity of the three backing buffers of this `RubyString` as much as possible.
+    pub fn shrink_to_fit(&mut self) {
+        self.packed_text.shrink_to_fit();
+        self.packed_ruby.shrink_to_fit();
+        self.placements.shrink_to_fit();
+    }
+
     ///Appends plain text (that does not have a ruby gloss attached to it) to this `RubyString`.
     pub fn push_str(&mut self, string: &str) {
         self.packed_text.push_str(string);
@@ -100,6 +236,64 @@ impl RubyString {
             .collect()
     }
 
+    ///Parses a `RubyString` from the interlinear annotation encoding produced by
+    ///[`to_interlinear_encoding`](#method.to_interlinear_encoding). This is the exact inverse of
+    ///that method.
+    ///
+    ///```
+    ///# use ruby_string::{RubyString, Segment};
+    ///let encoded = "ここは\u{FFF9}東\u{FFFA}とう\u{FFFB}\u{FFF9}京\u{FFFA}きょう\u{FFFB}です";
+    ///let rs = RubyString::from_interlinear_encoding(encoded).unwrap();
+    ///assert_eq!(rs.to_plain_text(), "ここは東京です");
+    ///assert_eq!(rs.to_interlinear_encoding(), encoded);
+    ///```
+    pub fn from_interlinear_encoding(encoded: &str) -> Result<RubyString, ParseError> {
+        let mut result = RubyString::new();
+        let mut state = InterlinearParseState::Normal(0);
+        for (offset, ch) in encoded.char_indices() {
+            match ch {
+                '\u{FFF9}' => match state {
+                    InterlinearParseState::Normal(start) => {
+                        result.push_str(&encoded[start..offset]);
+                        state = InterlinearParseState::Base(offset, offset + ch.len_utf8());
+                    }
+                    InterlinearParseState::Base(..) | InterlinearParseState::Ruby(..) => {
+                        return Err(ParseError::UnexpectedAnnotationAnchor(offset));
+                    }
+                },
+                '\u{FFFA}' => match state {
+                    InterlinearParseState::Base(anchor, start) => {
+                        let text = &encoded[start..offset];
+                        state = InterlinearParseState::Ruby(anchor, text, offset + ch.len_utf8());
+                    }
+                    InterlinearParseState::Normal(_) | InterlinearParseState::Ruby(..) => {
+                        return Err(ParseError::UnexpectedAnnotationSeparator(offset));
+                    }
+                },
+                '\u{FFFB}' => match state {
+                    InterlinearParseState::Ruby(_, text, ruby_start) => {
+                        let ruby = &encoded[ruby_start..offset];
+                        result.push_segment(Segment::Rubied { text, ruby });
+                        state = InterlinearParseState::Normal(offset + ch.len_utf8());
+                    }
+                    InterlinearParseState::Normal(_) | InterlinearParseState::Base(..) => {
+                        return Err(ParseError::UnexpectedAnnotationTerminator(offset));
+                    }
+                },
+                _ => {}
+            }
+        }
+        match state {
+            InterlinearParseState::Normal(start) => {
+                result.push_str(&encoded[start..]);
+                Ok(result)
+            }
+            InterlinearParseState::Base(anchor, _) | InterlinearParseState::Ruby(anchor, ..) => {
+                Err(ParseError::UnterminatedAnnotation(anchor))
+            }
+        }
+    }
+
     ///An iterator over the segments in this `RubyString`.
     pub fn segments(&self) -> SegmentIterator<'_> {
         SegmentIterator {
@@ -139,3 +333,38 @@ impl<'a> Extend<Segment<'a>> for RubyString {
         iter.into_iter().for_each(move |s| self.push_segment(s));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_interlinear_encoding_rejects_separator_without_anchor() {
+        let err = RubyString::from_interlinear_encoding("abc\u{FFFA}def").err();
+        assert_eq!(err, Some(ParseError::UnexpectedAnnotationSeparator(3)));
+    }
+
+    #[test]
+    fn from_interlinear_encoding_rejects_terminator_without_anchor() {
+        let err = RubyString::from_interlinear_encoding("abc\u{FFFB}def").err();
+        assert_eq!(err, Some(ParseError::UnexpectedAnnotationTerminator(3)));
+    }
+
+    #[test]
+    fn from_interlinear_encoding_rejects_nested_anchor() {
+        //the second anchor (at byte 6) is the one that was actually unexpected, not the
+        //still-open one at byte 0
+        let err =
+            RubyString::from_interlinear_encoding("\u{FFF9}abc\u{FFF9}xyz\u{FFFA}g\u{FFFB}").err();
+        assert_eq!(err, Some(ParseError::UnexpectedAnnotationAnchor(6)));
+    }
+
+    #[test]
+    fn from_interlinear_encoding_rejects_unterminated_annotation() {
+        let err = RubyString::from_interlinear_encoding("abc\u{FFF9}xyz").err();
+        assert_eq!(err, Some(ParseError::UnterminatedAnnotation(3)));
+
+        let err = RubyString::from_interlinear_encoding("abc\u{FFF9}xyz\u{FFFA}g").err();
+        assert_eq!(err, Some(ParseError::UnterminatedAnnotation(3)));
+    }
+}
@@ -0,0 +1,203 @@
+use crate::*;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+///Returns the display width (in terminal cells) that `segment` occupies when laid out by
+///[`wrap`](RubyString::wrap): for a plain run, the sum of the widths of its chars; for a rubied
+///segment, the wider of its base text and its gloss, since the gloss is rendered above the base
+///and may overhang it.
+fn segment_width(segment: &Segment<'_>) -> usize {
+    match *segment {
+        Segment::Plain { text } => UnicodeWidthStr::width(text),
+        Segment::Rubied { text, ruby } => {
+            UnicodeWidthStr::width(text).max(UnicodeWidthStr::width(ruby))
+        }
+    }
+}
+
+impl RubyString {
+    ///Wraps this `RubyString` to `max_width` terminal cells, returning the resulting lines as a
+    ///`Vec`. A [`Segment::Rubied`](enum.Segment.html#variant.Rubied) is never split across lines
+    ///— its base text and gloss always stay together — but a plain run may be split at
+    ///grapheme cluster boundaries to fill a line. If a single rubied segment is wider than
+    ///`max_width` on its own, it is emitted alone on its own line.
+    ///
+    ///```
+    ///# use ruby_string::{RubyString, Segment};
+    ///let mut rs = RubyString::new();
+    ///rs.push_str("ここは");
+    ///rs.push_segment(Segment::Rubied { text: "東京", ruby: "とうきょう" });
+    ///rs.push_str("です");
+    ///let lines = rs.wrap(6);
+    ///assert_eq!(lines.len(), 3);
+    ///assert_eq!(lines[0].to_plain_text(), "ここは");
+    ///assert_eq!(lines[1].to_plain_text(), "東京");
+    ///assert_eq!(lines[2].to_plain_text(), "です");
+    ///```
+    pub fn wrap(&self, max_width: usize) -> Vec<RubyString> {
+        self.wrap_iter(max_width).collect()
+    }
+
+    ///Like [`wrap`](#method.wrap), but returns a lazy iterator over the wrapped lines instead of
+    ///collecting them into a `Vec` up front.
+    pub fn wrap_iter(&self, max_width: usize) -> WrapIterator<'_> {
+        WrapIterator {
+            segments: self.segments(),
+            pending: None,
+            max_width,
+        }
+    }
+}
+
+///An iterator over the lines produced by wrapping a [`RubyString`](struct.RubyString.html) to a
+///maximum display width.
+///
+///This struct is created by the `wrap_iter` method on `RubyString`. See its documentation for
+///more.
+pub struct WrapIterator<'a> {
+    segments: SegmentIterator<'a>,
+    ///A segment (or the tail of one that got split) that was pulled from `segments` but did not
+    ///fit on the previous line.
+    pending: Option<Segment<'a>>,
+    max_width: usize,
+}
+
+impl<'a> WrapIterator<'a> {
+    fn next_segment(&mut self) -> Option<Segment<'a>> {
+        self.pending.take().or_else(|| self.segments.next())
+    }
+}
+
+impl<'a> Iterator for WrapIterator<'a> {
+    type Item = RubyString;
+
+    fn next(&mut self) -> Option<RubyString> {
+        let mut line = RubyString::new();
+        let mut line_width = 0;
+        let mut produced_anything = false;
+
+        while let Some(segment) = self.next_segment() {
+            let width = segment_width(&segment);
+            if line_width + width <= self.max_width {
+                line.push_segment(segment);
+                line_width += width;
+                produced_anything = true;
+                continue;
+            }
+
+            match segment {
+                Segment::Rubied { .. } => {
+                    if produced_anything {
+                        //this segment doesn't fit on the current line anymore - save it for the
+                        //next one
+                        self.pending = Some(segment);
+                    } else {
+                        //the segment does not fit into a whole line by itself - emit it alone
+                        line.push_segment(segment);
+                        produced_anything = true;
+                    }
+                    break;
+                }
+                Segment::Plain { text } => {
+                    let remaining = self.max_width.saturating_sub(line_width);
+                    let (fits, rest) = split_plain_text(text, remaining);
+                    if !fits.is_empty() {
+                        line.push_str(fits);
+                        produced_anything = true;
+                        if !rest.is_empty() {
+                            self.pending = Some(Segment::Plain { text: rest });
+                        }
+                    } else if produced_anything {
+                        //nothing of this segment fits on the current (non-empty) line - save
+                        //the whole segment for the next line
+                        self.pending = Some(Segment::Plain { text });
+                    } else {
+                        //not even a single grapheme cluster fits into max_width - emit it alone
+                        //to guarantee progress, mirroring the Segment::Rubied case above
+                        let split_at = text
+                            .grapheme_indices(true)
+                            .nth(1)
+                            .map_or(text.len(), |(offset, _)| offset);
+                        line.push_str(&text[..split_at]);
+                        produced_anything = true;
+                        if split_at < text.len() {
+                            self.pending = Some(Segment::Plain {
+                                text: &text[split_at..],
+                            });
+                        }
+                    }
+                    break;
+                }
+            }
+        }
+
+        if produced_anything {
+            Some(line)
+        } else {
+            None
+        }
+    }
+}
+
+///Splits `text` at the last grapheme cluster boundary whose accumulated width does not exceed
+///`max_width`, returning `(fitting_part, remainder)`.
+fn split_plain_text(text: &str, max_width: usize) -> (&str, &str) {
+    let mut width = 0;
+    for (offset, grapheme) in text.grapheme_indices(true) {
+        let grapheme_width: usize = grapheme.chars().filter_map(UnicodeWidthChar::width).sum();
+        if width + grapheme_width > max_width {
+            return (&text[..offset], &text[offset..]);
+        }
+        width += grapheme_width;
+    }
+    (text, "")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plain_lines(rs: &RubyString, max_width: usize) -> Vec<String> {
+        rs.wrap(max_width).iter().map(RubyString::to_plain_text).collect()
+    }
+
+    #[test]
+    fn wrap_zero_width() {
+        //max_width of 0 can never be satisfied, so every grapheme is emitted alone on its own
+        //line to guarantee progress instead of stalling the iterator
+        let rs = RubyString::from("abc");
+        assert_eq!(plain_lines(&rs, 0), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn wrap_grapheme_wider_than_max_width() {
+        //every "あ" is 2 cells wide, so none of them fit into a width-1 line on their own - each
+        //must still be emitted alone rather than stalling the iterator
+        let rs = RubyString::from("ああああ");
+        assert_eq!(plain_lines(&rs, 1), vec!["あ", "あ", "あ", "あ"]);
+    }
+
+    #[test]
+    fn wrap_oversized_grapheme_cluster_does_not_drop_trailing_text() {
+        //a ZWJ-joined emoji is a single grapheme cluster wider than max_width; it must be
+        //emitted alone, and the plain text following it must not be dropped
+        let rs = RubyString::from("\u{1F469}\u{200D}\u{1F467} hello");
+        assert_eq!(
+            plain_lines(&rs, 2),
+            vec!["\u{1F469}\u{200D}\u{1F467}", " h", "el", "lo"]
+        );
+    }
+
+    #[test]
+    fn wrap_rubied_segment_exactly_at_boundary() {
+        let mut rs = RubyString::new();
+        rs.push_segment(Segment::Rubied {
+            text: "東京",
+            ruby: "とうきょう",
+        });
+        rs.push_str("です");
+        //the rubied segment's width (10, from the 5-char gloss) exactly fills the line, so the
+        //following plain text must start on the next line rather than being appended to this one
+        assert_eq!(plain_lines(&rs, 10), vec!["東京", "です"]);
+    }
+}